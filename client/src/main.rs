@@ -1,5 +1,6 @@
 #![no_std]
 #![no_main]
+#![allow(async_fn_in_trait)]
 
 use{
     core::{
@@ -7,12 +8,13 @@ use{
         str::FromStr,
     },
     embassy_executor::Spawner,
-    embassy_net::{tcp::TcpSocket, Runner, StackResources, Config, DhcpConfig},
+    embassy_net::{tcp::TcpSocket, Runner, StackResources, Config, DhcpConfig, StaticConfigV4, Ipv4Cidr},
     embassy_time::{Duration, Timer, Instant, with_timeout},
     esp_hal::{clock::CpuClock, rng::Rng, timer::timg::TimerGroup},
     esp_println::println,
     esp_wifi::{
         init,
+        esp_now::{EspNow, PeerInfo},
         wifi::{
             ClientConfiguration,
             Configuration,
@@ -43,49 +45,212 @@ const WIFI_PASSWORD: &str = env!("WIFI_PASSWORD");
 const TCP_PORT: u16 = 1234;
 const REMOTE_ENDPOINT: (Ipv4Addr, u16) = (Ipv4Addr::new(192, 168, 65, 93), TCP_PORT);
 const CLIENT_NAME: &str = "ESP32-C3";
+const WIFI_CHANNEL: u8 = 5;
+
+/// Selectable wireless transport, chosen at build time. ESP-NOW talks directly
+/// at the WiFi MAC layer with no AP/DHCP/TCP, cutting association latency for
+/// the camera pan/tilt commands; TCP remains the default.
+enum Transport {
+    Tcp,
+    EspNow,
+}
+
+const TRANSPORT: Transport = match option_env!("TRANSPORT") {
+    Some(t) if matches!(t.as_bytes(), b"espnow") => Transport::EspNow,
+    _ => Transport::Tcp,
+};
+
+/// Parse the six hex octets of the build-time `PEER_MAC` value
+/// (e.g. `24:6f:28:aa:bb:cc`). Called from `main`, so the parse and its panics
+/// happen at run time, and only on the ESP-NOW path.
+const fn peer_mac() -> [u8; 6] {
+    let s = match option_env!("PEER_MAC") {
+        Some(s) => s.as_bytes(),
+        None => panic!("ESP-NOW transport requires PEER_MAC to be set"),
+    };
+    let mut mac = [0u8; 6];
+    let mut i = 0;
+    let mut idx = 0;
+    while i < 6 {
+        mac[i] = hex_nibble(s[idx]) << 4 | hex_nibble(s[idx + 1]);
+        idx += 3; // two hex digits + one separator
+        i += 1;
+    }
+    mac
+}
+
+const fn hex_nibble(c: u8) -> u8 {
+    match c {
+        b'0'..=b'9' => c - b'0',
+        b'a'..=b'f' => c - b'a' + 10,
+        b'A'..=b'F' => c - b'A' + 10,
+        _ => panic!("invalid hex digit in PEER_MAC"),
+    }
+}
 
 #[embassy_executor::task]
 async fn net_task(mut runner: Runner<'static, WifiDevice<'static, WifiStaDevice>>) {
     runner.run().await
 }
 
-#[embassy_executor::task]
-async fn connection(mut controller: WifiController<'static>) {
-    log::info!("Start Connection Task");
-    println!("Device capabilities: {:?}", controller.capabilities());
-    loop {
-        match esp_wifi::wifi::wifi_state() {
-            WifiState::StaConnected => {
-                // wait until we're no longer connected
-                controller.wait_for_event(WifiEvent::StaDisconnected).await;
-                Timer::after(Duration::from_millis(5000)).await
+/// A transport that exchanges servo-command frames, abstracting over TCP and
+/// ESP-NOW so the command loop below is written once.
+trait CommandLink {
+    /// Send one command frame.
+    async fn send(&mut self, data: &[u8]) -> Result<(), ()>;
+    /// Receive one reply into `buf`, returning the populated slice.
+    async fn recv<'a>(&mut self, buf: &'a mut [u8]) -> Result<&'a [u8], ()>;
+}
+
+/// TCP backend: a connected `TcpSocket`.
+struct TcpLink<'a, 'b> {
+    socket: &'a mut TcpSocket<'b>,
+}
+
+impl CommandLink for TcpLink<'_, '_> {
+    async fn send(&mut self, data: &[u8]) -> Result<(), ()> {
+        self.socket.write_all(data).await.map_err(|e| {
+            println!("write error: {:?}", e);
+        })
+    }
+
+    async fn recv<'a>(&mut self, buf: &'a mut [u8]) -> Result<&'a [u8], ()> {
+        match self.socket.read(buf).await {
+            Ok(0) => {
+                log::info!("read EOF");
+                Err(())
             }
-            _ => {}
+            Ok(n) => Ok(&buf[..n]),
+            Err(e) => {
+                log::warn!("read error: {:?}", e);
+                Err(())
+            }
+        }
+    }
+}
+
+/// ESP-NOW backend: a peer registered at the WiFi MAC layer, no AP/DHCP/TCP.
+struct EspNowLink {
+    esp_now: EspNow<'static>,
+    peer: [u8; 6],
+}
+
+impl CommandLink for EspNowLink {
+    async fn send(&mut self, data: &[u8]) -> Result<(), ()> {
+        self.esp_now.send_async(&self.peer, data).await.map_err(|e| {
+            log::warn!("ESP-NOW send error: {:?}", e);
+        })
+    }
+
+    async fn recv<'a>(&mut self, buf: &'a mut [u8]) -> Result<&'a [u8], ()> {
+        let received = self.esp_now.receive_async().await;
+        let data = received.data();
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok(&buf[..len])
+    }
+}
+
+/// Transport-agnostic command loop: emit the command frame, await the reply,
+/// print it. Returns when the link fails so the caller can re-establish it.
+async fn run_command_loop(link: &mut impl CommandLink) {
+    let mut buf = [0u8; 1024];
+    loop {
+        if link.send(b"Hello").await.is_err() {
+            return;
         }
-        if !matches!(controller.is_started(), Ok(true)) {
+        match link.recv(&mut buf).await {
+            Ok(data) => println!("{}", core::str::from_utf8(data).unwrap_or("<non-utf8>")),
+            Err(()) => return,
+        }
+        Timer::after(Duration::from_millis(1000)).await;
+    }
+}
+
+/// Managed WiFi association for this board's esp-wifi radio. The Pico W binary
+/// carries a sibling `WifiLink` with the same shape for its cyw43 backend; the
+/// two firmwares are separate crates with no shared lib, so the trait is kept in
+/// sync across them rather than shared. The [`supervise`] policy drives it below.
+trait WifiLink {
+    /// (Re)associate with the configured network.
+    async fn connect(&mut self) -> Result<(), ()>;
+    /// Resolve once the link drops from the connected state.
+    async fn wait_disconnected(&mut self);
+    /// Whether the link is currently associated.
+    fn is_connected(&self) -> bool;
+}
+
+/// esp-wifi backend: wraps the STA controller's start/connect state machine.
+struct EspWifiLink {
+    controller: WifiController<'static>,
+}
+
+impl WifiLink for EspWifiLink {
+    async fn connect(&mut self) -> Result<(), ()> {
+        if !matches!(self.controller.is_started(), Ok(true)) {
             let client_config = Configuration::Client(ClientConfiguration {
                 ssid: WIFI_NETWORK.try_into().unwrap(),
                 password: WIFI_PASSWORD.try_into().unwrap(),
-                channel: Some(5),
+                channel: Some(WIFI_CHANNEL),
                 ..Default::default()
             });
-            controller.set_configuration(&client_config).unwrap();
+            self.controller.set_configuration(&client_config).unwrap();
             log::info!("Starting wifi");
-            controller.start_async().await.unwrap();
+            self.controller.start_async().await.unwrap();
             log::info!("Wifi started!");
         }
-        println!("About to connect...");
+        self.controller.connect_async().await.map_err(|e| {
+            log::warn!("Failed to connect to wifi: {e:?}");
+        })
+    }
 
-        match controller.connect_async().await {
-            Ok(_) => log::info!("Wifi connected!"),
-            Err(e) => {
-                log::warn!("Failed to connect to wifi: {e:?}");
-                Timer::after(Duration::from_millis(5000)).await
+    async fn wait_disconnected(&mut self) {
+        self.controller.wait_for_event(WifiEvent::StaDisconnected).await;
+    }
+
+    fn is_connected(&self) -> bool {
+        matches!(esp_wifi::wifi::wifi_state(), WifiState::StaConnected)
+    }
+}
+
+/// Shortest and longest reconnect delays for the exponential backoff.
+const MIN_BACKOFF: Duration = Duration::from_millis(1000);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Shared supervisor policy: keep the link associated with exponential backoff
+/// and wait for the DHCP/static config to come up after each (re)connect.
+async fn supervise(mut link: impl WifiLink, stack: embassy_net::Stack<'static>) -> ! {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        if link.is_connected() {
+            link.wait_disconnected().await;
+            log::warn!("WiFi link down");
+            Timer::after(MIN_BACKOFF).await;
+        }
+
+        match link.connect().await {
+            Ok(()) => {
+                log::info!("WiFi connected!");
+                backoff = MIN_BACKOFF;
+                while !stack.is_config_up() {
+                    Timer::after_millis(100).await;
+                }
+                log::info!("Network config is Now Up!");
+            }
+            Err(()) => {
+                log::warn!("Retrying WiFi in {} ms", backoff.as_millis());
+                Timer::after(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
             }
         }
     }
 }
 
+#[embassy_executor::task]
+async fn wifi_supervisor(link: EspWifiLink, stack: embassy_net::Stack<'static>) -> ! {
+    supervise(link, stack).await
+}
+
 #[esp_hal_embassy::main]
 async fn main(spawner: Spawner) -> ! {
     esp_println::logger::init_logger_from_env();
@@ -103,15 +268,64 @@ async fn main(spawner: Spawner) -> ! {
     );
 
     let wifi = peripherals.WIFI;
-    let (wifi_interface, controller) =
-        esp_wifi::wifi::new_with_mode(&init, wifi, WifiStaDevice).unwrap();
 
     let timg1 = TimerGroup::new(peripherals.TIMG1);
     esp_hal_embassy::init(timg1.timer0);
 
-    let mut dhcp_config = DhcpConfig::default();
-    dhcp_config.hostname = Some(heapless::String::from_str(CLIENT_NAME).unwrap());
-    let config = Config::dhcpv4(dhcp_config);
+    // ESP-NOW bypasses the AP/DHCP/TCP stack entirely: bring it up here and run
+    // the command loop directly over the WiFi MAC layer, reusing the same
+    // command bytes the TCP path sends.
+    if matches!(TRANSPORT, Transport::EspNow) {
+        let peer = peer_mac();
+        let mut esp_now = EspNow::new(&init, wifi).unwrap();
+        log::info!("ESP-NOW up, peer {:?}", peer);
+        esp_now
+            .add_peer(PeerInfo {
+                peer_address: peer,
+                lmk: None,
+                channel: Some(WIFI_CHANNEL),
+                encrypt: false,
+            })
+            .unwrap();
+
+        let mut link = EspNowLink { esp_now, peer };
+        loop {
+            run_command_loop(&mut link).await;
+            Timer::after(Duration::from_millis(1000)).await;
+        }
+    }
+
+    let (wifi_interface, controller) =
+        esp_wifi::wifi::new_with_mode(&init, wifi, WifiStaDevice).unwrap();
+
+    // Use a static IPv4 configuration when STATIC_IP/GATEWAY_IP are provided at
+    // build time, otherwise fall back to DHCP (the default). This keeps the fixed
+    // REMOTE_ENDPOINT reachable on networks without a DHCP server.
+    let config = match (option_env!("STATIC_IP"), option_env!("GATEWAY_IP")) {
+        (Some(ip), Some(gw)) => {
+            let prefix_len: u8 = match option_env!("STATIC_PREFIX_LEN") {
+                Some(s) => s.parse().expect("STATIC_PREFIX_LEN must be an integer in 0..=32"),
+                None => 24,
+            };
+            assert!(prefix_len <= 32, "STATIC_PREFIX_LEN must be <= 32");
+            let address = Ipv4Cidr::new(
+                Ipv4Addr::from_str(ip).expect("STATIC_IP must be a valid IPv4 address"),
+                prefix_len,
+            );
+            let gateway = Some(Ipv4Addr::from_str(gw).expect("GATEWAY_IP must be a valid IPv4 address"));
+            log::info!("Using static IP {:?}/{}", address.address(), prefix_len);
+            Config::ipv4_static(StaticConfigV4 {
+                address,
+                gateway,
+                dns_servers: heapless::Vec::new(),
+            })
+        }
+        _ => {
+            let mut dhcp_config = DhcpConfig::default();
+            dhcp_config.hostname = Some(heapless::String::from_str(CLIENT_NAME).unwrap());
+            Config::dhcpv4(dhcp_config)
+        }
+    };
 
     let seed = (rng.random() as u64) << 32 | rng.random() as u64;
 
@@ -123,32 +337,14 @@ async fn main(spawner: Spawner) -> ! {
         seed,
     );
 
-    spawner.must_spawn(connection(controller));
+    spawner.must_spawn(wifi_supervisor(EspWifiLink { controller }, stack));
     spawner.must_spawn(net_task(runner));
 
-    log::info!("Waiting for DHCP...");
-    while !stack.is_config_up() {
-        Timer::after_millis(100).await;
-    }
-    log::info!("DHCP is Now Up!");
-
-    match stack.config_v4(){
-        Some(value) => {
-            log::info!("Server Address: {:?}", value.address.address());
-            log::info!("Gateway {:?}", value.gateway);
-            log::info!("DNS Server {:?}", value.dns_servers);
-        },
-        None => {
-            log::warn!("Unable to Get the Adrress");
-        }
-    };
-
     let mut rx_buffer = [0; 4096];
     let mut tx_buffer = [0; 4096];
-    let mut buf = [0; 1024];
 
     loop {
-        let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);     
+        let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
 
         match stack.config_v4(){
             Some(value) => {
@@ -183,28 +379,8 @@ async fn main(spawner: Spawner) -> ! {
 
         log::info!("Connected to Endpoint!");
 
-        loop {
-            let r = socket.write_all(b"Hello").await;
-
-            if let Err(e) = r {
-                println!("write error: {:?}", e);
-                break;
-            }
-            
-            let n = match socket.read(&mut buf).await {
-                Ok(0) => {
-                    log::info!("read EOF");
-                    break;
-                }
-                Ok(n) => n,
-                Err(e) => {
-                    log::warn!("read error: {:?}", e);
-                    break;
-                }
-            };
-
-            println!("{}", core::str::from_utf8(&buf[..n]).unwrap());
-            Timer::after(Duration::from_millis(1000)).await;
-        }
+        // Same transport-agnostic handler as the ESP-NOW path.
+        let mut link = TcpLink { socket: &mut socket };
+        run_command_loop(&mut link).await;
     }
 }
\ No newline at end of file
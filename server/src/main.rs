@@ -17,18 +17,31 @@ use {
         NetworkResources,
         DisplayResources,
     },
-    // crate::tasks::{
-    //     servo_pio::servo_pio,
-    //     servo_pio::Command as ServoCommand,
-    //     servo_pio::send_command as send_servo,
-    // },
+    crate::tasks::servo_pio::{
+        servo_pio,
+        send_command as send_servo,
+        ack_frame,
+        nak_frame,
+        FrameDecoder,
+    },
+    #[cfg(not(feature = "w5500"))]
     cyw43::JoinOptions,
+    #[cfg(not(feature = "w5500"))]
     cyw43_pio::{PioSpi, DEFAULT_CLOCK_DIVIDER},
-    
+
+    #[cfg(feature = "w5500")]
+    embassy_net_wiznet::{chip::W5500, Device as W5500Device, Runner as W5500Runner, State as W5500State},
+    #[cfg(feature = "w5500")]
+    embassy_time::Delay,
+    #[cfg(feature = "w5500")]
+    embedded_hal_bus::spi::ExclusiveDevice,
+
+
     embassy_executor::Spawner,
     embassy_time::{
-        Duration, 
+        Duration,
         Timer,
+        Instant,
         with_timeout,
     },
     embassy_net::{
@@ -36,6 +49,8 @@ use {
         Config,
         StackResources,
         DhcpConfig,
+        StaticConfigV4,
+        Ipv4Cidr,
     },
     embassy_rp::{
         clocks::RoscRng,
@@ -44,9 +59,15 @@ use {
         pio::Pio,
         usb::Driver as UsbDriver,
     },
+    #[cfg(feature = "w5500")]
+    embassy_rp::{
+        gpio::{Input, Pull},
+        spi::{Config as SpiConfig, Spi},
+    },
 
     embedded_io_async::Write,
-    core::str::{from_utf8, FromStr},
+    core::net::Ipv4Addr,
+    core::str::FromStr,
     rand::RngCore,
     static_cell::StaticCell,
     defmt::*,
@@ -58,69 +79,308 @@ const WIFI_PASSWORD: &str = env!("WIFI_PASSWORD");
 const CLIENT_NAME: &str = "Pico-W";
 const TCP_PORT: u16 = 1234;
 
+// When built with BENCH set, each accepted connection runs a TX/RX throughput
+// test instead of the servo-command loop. Pair it with the desktop
+// perf-server in tools/perf-server.
+const BENCH_MODE: bool = option_env!("BENCH").is_some();
+const BENCH_WINDOW: Duration = Duration::from_secs(10);
+
+/// Run a two-phase throughput test over an established socket: first blast the
+/// buffer for a fixed wall-clock window (TX), then drain whatever the peer sends
+/// for the same window (RX), logging the achieved rate for each phase.
+async fn run_throughput_benchmark(socket: &mut TcpSocket<'_>, buf: &mut [u8]) {
+    // Clear the accept-path timeout so neither phase aborts before the bench
+    // window elapses; the duplex peer keeps both directions flowing.
+    socket.set_timeout(None);
+
+    let start = Instant::now();
+    let mut tx_bytes: u64 = 0;
+    while Instant::now() - start < BENCH_WINDOW {
+        match socket.write_all(buf).await {
+            Ok(()) => tx_bytes += buf.len() as u64,
+            Err(e) => {
+                log::warn!("Bench TX write error: {:?}", e);
+                return;
+            }
+        }
+    }
+    log_mbps("TX", tx_bytes, Instant::now() - start);
+
+    let start = Instant::now();
+    let mut rx_bytes: u64 = 0;
+    while Instant::now() - start < BENCH_WINDOW {
+        match socket.read(buf).await {
+            Ok(0) => break,
+            Ok(n) => rx_bytes += n as u64,
+            Err(e) => {
+                log::warn!("Bench RX read error: {:?}", e);
+                break;
+            }
+        }
+    }
+    log_mbps("RX", rx_bytes, Instant::now() - start);
+}
+
+fn log_mbps(phase: &str, bytes: u64, elapsed: Duration) {
+    let secs = elapsed.as_micros() as f64 / 1_000_000.0;
+    let mbps = if secs > 0.0 {
+        (bytes as f64 * 8.0) / (secs * 1_000_000.0)
+    } else {
+        0.0
+    };
+    log::info!(
+        "Bench {} phase: {} bytes in {} ms = {} Mbps",
+        phase,
+        bytes,
+        elapsed.as_millis(),
+        mbps
+    );
+}
+
 #[embassy_executor::task]
 async fn logger_task(driver: UsbDriver<'static, USB>) {
     embassy_usb_logger::run!(1024, log::LevelFilter::Info, driver);
 }
 
+#[cfg(not(feature = "w5500"))]
 #[embassy_executor::task]
 async fn cyw43_task(runner: cyw43::Runner<'static, Output<'static>, PioSpi<'static, PIO0, 0, DMA_CH0>>) -> ! {
     runner.run().await
 }
 
+#[cfg(not(feature = "w5500"))]
 #[embassy_executor::task]
 async fn net_task(mut runner: embassy_net::Runner<'static, cyw43::NetDriver<'static>>) -> ! {
     runner.run().await
 }
 
+#[cfg(feature = "w5500")]
+#[embassy_executor::task]
+async fn ethernet_task(
+    runner: W5500Runner<
+        'static,
+        ExclusiveDevice<Spi<'static, embassy_rp::peripherals::SPI0, embassy_rp::spi::Async>, Output<'static>, Delay>,
+        Input<'static>,
+        Output<'static>,
+    >,
+) -> ! {
+    runner.run().await
+}
+
+#[cfg(feature = "w5500")]
+#[embassy_executor::task]
+async fn net_task(mut runner: embassy_net::Runner<'static, W5500Device<'static>>) -> ! {
+    runner.run().await
+}
+
+/// Managed WiFi association for this board's cyw43 radio. The companion ESP32-C3
+/// binary defines a sibling `WifiLink` trait with the same shape for its esp-wifi
+/// backend; because the two firmwares are separate crates with no shared lib, the
+/// "single trait across both boards" is achieved by keeping the definitions in
+/// sync rather than sharing one. The [`supervise`] policy drives it identically.
+#[cfg(not(feature = "w5500"))]
+trait WifiLink {
+    /// (Re)associate with the configured network.
+    async fn connect(&mut self) -> Result<(), ()>;
+    /// Resolve once the link drops from the connected state.
+    async fn wait_disconnected(&mut self);
+    /// Whether the link is currently associated.
+    fn is_connected(&self) -> bool;
+}
+
+/// cyw43 backend: wraps `control.join` and mirrors association state onto the
+/// onboard status LED.
+#[cfg(not(feature = "w5500"))]
+struct Cyw43Link {
+    control: cyw43::Control<'static>,
+    stack: embassy_net::Stack<'static>,
+}
+
+#[cfg(not(feature = "w5500"))]
+impl WifiLink for Cyw43Link {
+    async fn connect(&mut self) -> Result<(), ()> {
+        self.control.gpio_set(0, false).await;
+        match self
+            .control
+            .join(WIFI_NETWORK, JoinOptions::new(WIFI_PASSWORD.as_bytes()))
+            .await
+        {
+            Ok(_) => {
+                self.control.gpio_set(0, true).await;
+                Ok(())
+            }
+            Err(err) => {
+                log::info!("Join failed with error = {:?}", err);
+                Err(())
+            }
+        }
+    }
+
+    async fn wait_disconnected(&mut self) {
+        // cyw43 has no link-down event on Control, but it drives association
+        // state into the net device, so poll the stack's link flag.
+        while self.stack.is_link_up() {
+            Timer::after(Duration::from_secs(1)).await;
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.stack.is_link_up()
+    }
+}
+
+/// Shortest and longest reconnect delays for the exponential backoff.
+#[cfg(not(feature = "w5500"))]
+const MIN_BACKOFF: Duration = Duration::from_millis(1000);
+#[cfg(not(feature = "w5500"))]
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Shared supervisor policy: keep the link associated with exponential backoff
+/// and wait for the network config to come up after each (re)connect.
+#[cfg(not(feature = "w5500"))]
+async fn supervise(mut link: impl WifiLink, stack: embassy_net::Stack<'static>) -> ! {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        if link.is_connected() {
+            link.wait_disconnected().await;
+            log::warn!("WiFi link down");
+            Timer::after(MIN_BACKOFF).await;
+        }
+
+        match link.connect().await {
+            Ok(()) => {
+                log::info!("WiFi connected!");
+                backoff = MIN_BACKOFF;
+                while !stack.is_config_up() {
+                    Timer::after_millis(100).await;
+                }
+                log::info!("DHCP is Now Up!");
+            }
+            Err(()) => {
+                log::warn!("Retrying WiFi in {} ms", backoff.as_millis());
+                Timer::after(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "w5500"))]
+#[embassy_executor::task]
+async fn wifi_supervisor(link: Cyw43Link, stack: embassy_net::Stack<'static>) -> ! {
+    supervise(link, stack).await
+}
+
 #[embassy_executor::main]
 async fn main(spawner: Spawner) -> ! {
     let pheriperals = embassy_rp::init(Default::default());
     let usb_driver = UsbDriver::new(pheriperals.USB, Irqs);
     let r = split_resources!(pheriperals);
     let p = r.network_resources;
-    let mut led_status = false;
-    
+
     unwrap!(spawner.spawn(logger_task(usb_driver)));
-    // unwrap!(spawner.spawn(servo_pio(r.servo_pio_resources)));
+    unwrap!(spawner.spawn(servo_pio(r.servo_pio_resources)));
     
     log::info!("Preparing the Server!");
 
     let mut rng = RoscRng;
-    let fw = include_bytes!("../cyw43-firmware/43439A0.bin");
-    let clm = include_bytes!("../cyw43-firmware/43439A0_clm.bin");
-
-    let pwr = Output::new(p.CYW43_PWR_PIN, Level::Low);
-    let cs = Output::new(p.CYW43_CS_PIN, Level::High);
-    let mut pio = Pio::new(p.CYW43_PIO_CH, Irqs);
-    let spi = PioSpi::new(
-        &mut pio.common, 
-        pio.sm0, 
-        DEFAULT_CLOCK_DIVIDER,
-        pio.irq0, 
-        cs, 
-        p.CYW43_SPI_DIO, 
-        p.CYW43_SPI_CLK, 
-        p.CYW43_DMA_CH
-    );
 
-    static STATE: StaticCell<cyw43::State> = StaticCell::new();
-    let state = STATE.init(cyw43::State::new());
-    let (net_device, mut control, runner) = cyw43::new(state, pwr, spi, fw).await;
-    unwrap!(spawner.spawn(cyw43_task(runner)));
+    #[cfg(not(feature = "w5500"))]
+    let (net_device, control) = {
+        let fw = include_bytes!("../cyw43-firmware/43439A0.bin");
+        let clm = include_bytes!("../cyw43-firmware/43439A0_clm.bin");
+
+        let pwr = Output::new(p.CYW43_PWR_PIN, Level::Low);
+        let cs = Output::new(p.CYW43_CS_PIN, Level::High);
+        let mut pio = Pio::new(p.CYW43_PIO_CH, Irqs);
+        let spi = PioSpi::new(
+            &mut pio.common,
+            pio.sm0,
+            DEFAULT_CLOCK_DIVIDER,
+            pio.irq0,
+            cs,
+            p.CYW43_SPI_DIO,
+            p.CYW43_SPI_CLK,
+            p.CYW43_DMA_CH,
+        );
+
+        static STATE: StaticCell<cyw43::State> = StaticCell::new();
+        let state = STATE.init(cyw43::State::new());
+        let (net_device, mut control, runner) = cyw43::new(state, pwr, spi, fw).await;
+        unwrap!(spawner.spawn(cyw43_task(runner)));
 
-    control.init(clm).await;
-    control
-        .set_power_management(cyw43::PowerManagementMode::Aggressive)
-        .await;
+        control.init(clm).await;
+        control
+            .set_power_management(cyw43::PowerManagementMode::Aggressive)
+            .await;
 
-    log::info!("CYW43 has been set!");    
-    control.gpio_set(0, true).await;
+        log::info!("CYW43 has been set!");
+        control.gpio_set(0, true).await;
+        (net_device, control)
+    };
+
+    // Wired Ethernet: drive a WIZnet W5500 in MACRAW mode over the SPI pins
+    // exposed by NetworkResources instead of bringing up cyw43. The resulting
+    // Device feeds the same embassy_net stack below, so the accept/servo loop is
+    // unchanged.
+    #[cfg(feature = "w5500")]
+    let net_device = {
+        let mut spi_config = SpiConfig::default();
+        spi_config.frequency = 50_000_000;
+        let spi = Spi::new(
+            p.W5500_SPI,
+            p.W5500_SPI_CLK,
+            p.W5500_SPI_MOSI,
+            p.W5500_SPI_MISO,
+            p.W5500_DMA_TX,
+            p.W5500_DMA_RX,
+            spi_config,
+        );
+        let cs = Output::new(p.W5500_CS_PIN, Level::High);
+        let spi_dev = ExclusiveDevice::new(spi, cs, Delay).unwrap();
+
+        let int = Input::new(p.W5500_INT_PIN, Pull::Up);
+        let reset = Output::new(p.W5500_RESET_PIN, Level::High);
+
+        static STATE: StaticCell<W5500State<8, 8>> = StaticCell::new();
+        let state = STATE.init(W5500State::<8, 8>::new());
+        // Locally administered MAC; override per-deployment if required.
+        let mac_addr = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+        let (net_device, runner) =
+            embassy_net_wiznet::new::<W5500, _, _, _>(mac_addr, state, spi_dev, int, reset).await;
+        unwrap!(spawner.spawn(ethernet_task(runner)));
+        log::info!("W5500 has been set!");
+        net_device
+    };
 
-    // Using DHCP config for the ipv4 address
-    let mut dhcp_config = DhcpConfig::default();
-    dhcp_config.hostname = Some(heapless::String::from_str(CLIENT_NAME).unwrap());
-    let config = Config::dhcpv4(dhcp_config);
+    // Use a static IPv4 configuration when STATIC_IP/GATEWAY_IP are provided at
+    // build time, otherwise fall back to DHCP (the default).
+    let config = match (option_env!("STATIC_IP"), option_env!("GATEWAY_IP")) {
+        (Some(ip), Some(gw)) => {
+            let prefix_len: u8 = match option_env!("STATIC_PREFIX_LEN") {
+                Some(s) => s.parse().expect("STATIC_PREFIX_LEN must be an integer in 0..=32"),
+                None => 24,
+            };
+            assert!(prefix_len <= 32, "STATIC_PREFIX_LEN must be <= 32");
+            let address = Ipv4Cidr::new(
+                Ipv4Addr::from_str(ip).expect("STATIC_IP must be a valid IPv4 address"),
+                prefix_len,
+            );
+            let gateway = Some(Ipv4Addr::from_str(gw).expect("GATEWAY_IP must be a valid IPv4 address"));
+            log::info!("Using static IP {:?}/{}", address.address(), prefix_len);
+            Config::ipv4_static(StaticConfigV4 {
+                address,
+                gateway,
+                dns_servers: heapless::Vec::new(),
+            })
+        }
+        _ => {
+            // Using DHCP config for the ipv4 address
+            let mut dhcp_config = DhcpConfig::default();
+            dhcp_config.hostname = Some(heapless::String::from_str(CLIENT_NAME).unwrap());
+            Config::dhcpv4(dhcp_config)
+        }
+    };
 
     // Generate random seed
     let seed = rng.next_u64();
@@ -131,29 +391,20 @@ async fn main(spawner: Spawner) -> ! {
 
     unwrap!(spawner.spawn(net_task(runner)));
     
-    // Connecting to the Network
-    control.gpio_set(0, false).await;
-    loop {
-        match control.join(WIFI_NETWORK, JoinOptions::new(WIFI_PASSWORD.as_bytes())).await {
-            Ok(_) => {
-                control.gpio_set(0, true).await;
-                break
-            },
-            Err(err) => {
-                if err.status<16 {
-                    control.gpio_set(0, false).await;
-                    log::info!("Join failed with error = {:?}", err);
-                }
-            }
-        }
-    }
+    // WiFi association, reconnect-with-backoff and DHCP-up waiting are owned by
+    // the shared supervisor task (cyw43 only; the W5500 path is wired).
+    #[cfg(not(feature = "w5500"))]
+    unwrap!(spawner.spawn(wifi_supervisor(Cyw43Link { control, stack }, stack)));
 
-    // Wait for DHCP, not necessary when using static IP
-    info!("Waiting for DHCP...");
-    while !stack.is_config_up() {
-        Timer::after_millis(100).await;
+    // Wired links have no association step, so wait for the config here.
+    #[cfg(feature = "w5500")]
+    {
+        info!("Waiting for DHCP...");
+        while !stack.is_config_up() {
+            Timer::after_millis(100).await;
+        }
+        log::info!("DHCP is Now Up!");
     }
-    log::info!("DHCP is Now Up!");
 
     match stack.config_v4(){
         Some(value) => {
@@ -194,33 +445,32 @@ async fn main(spawner: Spawner) -> ! {
                     }
                     Err(e) => {
                         log::warn!("Accept Error: {:?}", e);
-                        control.gpio_set(0, led_status).await;
-                        led_status  = !led_status;
                         continue;
                     }
                 }
             }
             Err(_) => {
                 log::warn!("No Connection after 5s");
-                control.gpio_set(0, led_status).await;
-                led_status  = !led_status;
                 continue;
             }
         }
 
         log::info!("Received Connection from {:?}", socket.remote_endpoint());
-        control.gpio_set(0, true).await;
 
-        loop {
+        if BENCH_MODE {
+            run_throughput_benchmark(&mut socket, &mut buf).await;
+            continue;
+        }
+
+        let mut decoder = FrameDecoder::new();
+
+        'session: loop {
             let n = match socket.read(&mut buf).await {
                 Ok(0) => {
                     log::warn!("[Read EOF]: Connection is Closed");
                     break;
                 }
-                Ok(n) => {
-                    // Next --> Parse the command
-                    n
-                },
+                Ok(n) => n,
                 Err(e) => {
                     log::warn!("Read Error: {:?}", e);
                     log::warn!("Connection is Closed");
@@ -228,24 +478,31 @@ async fn main(spawner: Spawner) -> ! {
                 }
             };
 
-            log::info!("rxd {}", from_utf8(&buf[..n]).unwrap());
-            // send_servo(ServoCommand::Left(90));
-            // Timer::after_millis(100).await;
-            // send_servo(ServoCommand::Right(90));
-            // Timer::after_millis(100).await;
-            // send_servo(ServoCommand::Up(90));
-            // Timer::after_millis(100).await;
-            // send_servo(ServoCommand::Down(90));
-            // Timer::after_millis(100).await;
-
-            match socket.write_all(&buf[..n]).await {
-                Ok(()) => {}
-                Err(e) => {
+            // Feed each byte through the decoder; it buffers partial frames and
+            // resyncs on a bad checksum, so coalesced and split frames both work.
+            for &b in &buf[..n] {
+                let Some(result) = decoder.push_byte(b) else {
+                    continue;
+                };
+                let reply = match result {
+                    Ok(cmd) => {
+                        log::info!("cmd {:?}", cmd);
+                        let axis = cmd.axis();
+                        send_servo(cmd);
+                        ack_frame(axis)
+                    }
+                    Err(reason) => {
+                        log::warn!("Rejected frame: {:?}", reason);
+                        nak_frame(reason)
+                    }
+                };
+
+                if let Err(e) = socket.write_all(&reply).await {
                     log::warn!("Write Error: {:?}", e);
                     log::warn!("Connection is Closed");
-                    break;
+                    break 'session;
                 }
-            };
+            }
         }
     }
 }
\ No newline at end of file
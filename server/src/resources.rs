@@ -0,0 +1 @@
+pub mod gpio_list;
@@ -0,0 +1,51 @@
+//! Peripheral and pin assignments for the Pico W server, split into per-task
+//! resource groups via `assign_resources!`.
+
+use {
+    assign_resources::assign_resources,
+    embassy_rp::{
+        bind_interrupts,
+        peripherals,
+        pio::InterruptHandler as PioInterruptHandler,
+        usb::InterruptHandler as UsbInterruptHandler,
+    },
+};
+
+bind_interrupts!(pub struct Irqs {
+    USBCTRL_IRQ => UsbInterruptHandler<peripherals::USB>;
+    PIO0_IRQ_0 => PioInterruptHandler<peripherals::PIO0>;
+    PIO1_IRQ_0 => PioInterruptHandler<peripherals::PIO1>;
+});
+
+assign_resources! {
+    // CYW43 (default) plus the optional WIZnet W5500 SPI pins used by the
+    // `w5500` feature. Only one backend is wired up at a time, so the unused
+    // group's peripherals simply go unclaimed.
+    network_resources: NetworkResources {
+        CYW43_PWR_PIN: PIN_23,
+        CYW43_CS_PIN: PIN_25,
+        CYW43_PIO_CH: PIO0,
+        CYW43_SPI_DIO: PIN_24,
+        CYW43_SPI_CLK: PIN_29,
+        CYW43_DMA_CH: DMA_CH0,
+
+        W5500_SPI: SPI0,
+        W5500_SPI_CLK: PIN_18,
+        W5500_SPI_MOSI: PIN_19,
+        W5500_SPI_MISO: PIN_20,
+        W5500_CS_PIN: PIN_21,
+        W5500_INT_PIN: PIN_22,
+        W5500_RESET_PIN: PIN_26,
+        W5500_DMA_TX: DMA_CH1,
+        W5500_DMA_RX: DMA_CH2,
+    }
+    servo_pio_resources: ServoPioResources {
+        SERVO_PIO_CH: PIO1,
+        PAN_PIN: PIN_16,
+        TILT_PIN: PIN_17,
+    }
+    display_resources: DisplayResources {
+        SDA_PIN: PIN_14,
+        SCL_PIN: PIN_15,
+    }
+}
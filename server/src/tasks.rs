@@ -0,0 +1 @@
+pub mod servo_pio;
@@ -0,0 +1,250 @@
+//! PIO-driven 2-DOF servo control and the wire protocol used to command it.
+//!
+//! Commands arrive over the TCP socket as a compact binary frame; this module
+//! owns both the parser (which tolerates TCP's byte-stream framing) and the PIO
+//! task that turns decoded commands into servo pulses.
+
+use {
+    crate::resources::gpio_list::ServoPioResources,
+    embassy_rp::pio::Pio,
+    embassy_rp::pio_programs::pwm::{PioPwm, PioPwmProgram},
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    embassy_sync::channel::Channel,
+    embassy_time::Duration,
+    crate::resources::gpio_list::Irqs,
+};
+
+/// Which of the two axes a command targets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    Pan,
+    Tilt,
+}
+
+/// A decoded servo command, ready to enqueue to the PIO task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServoCommand {
+    /// Move the axis to an absolute angle in centidegrees (0..=18000).
+    Absolute { axis: Axis, centideg: u16 },
+    /// Nudge the axis by a signed delta in centidegrees.
+    Relative { axis: Axis, delta: i16 },
+    /// Return the axis to its centre (home) position.
+    Home { axis: Axis },
+}
+
+impl ServoCommand {
+    /// The axis this command targets.
+    pub fn axis(&self) -> Axis {
+        match self {
+            ServoCommand::Absolute { axis, .. }
+            | ServoCommand::Relative { axis, .. }
+            | ServoCommand::Home { axis } => *axis,
+        }
+    }
+}
+
+/// Reason a frame was rejected, echoed back to the client in a NAK.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NakReason {
+    BadChecksum = 1,
+    BadAxis = 2,
+    BadOpcode = 3,
+    OutOfRange = 4,
+}
+
+/// On-wire frame length: axis, opcode, angle hi/lo, checksum.
+pub const FRAME_LEN: usize = 5;
+const MAX_CENTIDEG: u16 = 18_000;
+
+const AXIS_PAN: u8 = 0x00;
+const AXIS_TILT: u8 = 0x01;
+
+impl Axis {
+    /// The on-wire selector byte for this axis.
+    fn wire(self) -> u8 {
+        match self {
+            Axis::Pan => AXIS_PAN,
+            Axis::Tilt => AXIS_TILT,
+        }
+    }
+}
+
+const OP_ABSOLUTE: u8 = 0x00;
+const OP_RELATIVE: u8 = 0x01;
+const OP_HOME: u8 = 0x02;
+
+/// Reply opcodes.
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+
+/// Bounded queue of decoded commands handed to the PIO task. `try_send` keeps the
+/// socket read loop non-blocking; when the queue is full the incoming command is
+/// dropped and the already-queued ones are preserved.
+pub static COMMAND_CHANNEL: Channel<CriticalSectionRawMutex, ServoCommand, 8> = Channel::new();
+
+/// Enqueue a command for the PIO task. Non-blocking by design: the network loop
+/// must never stall on servo motion.
+pub fn send_command(cmd: ServoCommand) {
+    let _ = COMMAND_CHANNEL.try_send(cmd);
+}
+
+/// Build a two-byte ACK reply naming the acknowledged axis.
+pub fn ack_frame(axis: Axis) -> [u8; 2] {
+    [ACK, axis.wire()]
+}
+
+/// Build a two-byte NAK reply carrying the rejection reason.
+pub fn nak_frame(reason: NakReason) -> [u8; 2] {
+    [NAK, reason as u8]
+}
+
+/// XOR checksum over the four payload bytes of a frame.
+fn checksum(frame: &[u8]) -> u8 {
+    frame[0] ^ frame[1] ^ frame[2] ^ frame[3]
+}
+
+/// Reassembles frames from the TCP byte stream. A single `read` may split a frame
+/// across calls or coalesce several frames together, so incoming bytes are
+/// accumulated here and consumed one complete frame at a time.
+pub struct FrameDecoder {
+    buf: [u8; FRAME_LEN],
+    len: usize,
+}
+
+impl FrameDecoder {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; FRAME_LEN],
+            len: 0,
+        }
+    }
+
+    /// Feed a single byte from the TCP stream, emitting a decoded command (or a
+    /// NAK reason) once a full frame has accumulated.
+    ///
+    /// Partial frames are retained across calls, so split and coalesced frames
+    /// both survive. Because the framing is fixed-length with no delimiter, a bad
+    /// checksum means the stream is misaligned: we drop the leading byte and
+    /// re-examine the remainder, so the decoder resynchronises on the next valid
+    /// frame boundary instead of misframing forever.
+    pub fn push_byte(&mut self, b: u8) -> Option<Result<ServoCommand, NakReason>> {
+        self.buf[self.len] = b;
+        self.len += 1;
+        if self.len < FRAME_LEN {
+            return None;
+        }
+
+        let frame = self.buf;
+        match decode_frame(&frame) {
+            Err(NakReason::BadChecksum) => {
+                // Realign: discard one byte and keep the tail for the next attempt.
+                self.buf.copy_within(1..FRAME_LEN, 0);
+                self.len = FRAME_LEN - 1;
+                Some(Err(NakReason::BadChecksum))
+            }
+            result => {
+                // Checksum held, so the frame was aligned; consume it whole.
+                self.len = 0;
+                Some(result)
+            }
+        }
+    }
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn decode_frame(frame: &[u8; FRAME_LEN]) -> Result<ServoCommand, NakReason> {
+    if frame[FRAME_LEN - 1] != checksum(frame) {
+        return Err(NakReason::BadChecksum);
+    }
+
+    let axis = match frame[0] {
+        AXIS_PAN => Axis::Pan,
+        AXIS_TILT => Axis::Tilt,
+        _ => return Err(NakReason::BadAxis),
+    };
+
+    let raw = u16::from_be_bytes([frame[2], frame[3]]);
+
+    match frame[1] {
+        OP_ABSOLUTE => {
+            if raw > MAX_CENTIDEG {
+                return Err(NakReason::OutOfRange);
+            }
+            Ok(ServoCommand::Absolute { axis, centideg: raw })
+        }
+        OP_RELATIVE => Ok(ServoCommand::Relative {
+            axis,
+            delta: raw as i16,
+        }),
+        OP_HOME => Ok(ServoCommand::Home { axis }),
+        _ => Err(NakReason::BadOpcode),
+    }
+}
+
+/// Map a centidegree angle to a servo pulse width. Standard hobby servos sweep
+/// 0..=180 degrees over a 500..=2500 us pulse on a 20 ms period.
+fn pulse_for(centideg: u16) -> Duration {
+    let clamped = centideg.min(MAX_CENTIDEG) as u32;
+    let us = 500 + (clamped * 2000) / MAX_CENTIDEG as u32;
+    Duration::from_micros(us as u64)
+}
+
+const PERIOD: Duration = Duration::from_millis(20);
+
+/// PIO task: drive the pan/tilt servos, tracking each axis' current angle so
+/// relative and home commands can be resolved.
+#[embassy_executor::task]
+pub async fn servo_pio(r: ServoPioResources) {
+    let Pio {
+        mut common,
+        sm0,
+        sm1,
+        ..
+    } = Pio::new(r.SERVO_PIO_CH, Irqs);
+
+    let program = PioPwmProgram::new(&mut common);
+    let mut pan = PioPwm::new(&mut common, sm0, r.PAN_PIN, &program);
+    let mut tilt = PioPwm::new(&mut common, sm1, r.TILT_PIN, &program);
+    pan.set_period(PERIOD);
+    tilt.set_period(PERIOD);
+    pan.start();
+    tilt.start();
+
+    // Start both axes centred.
+    let mut pan_angle = MAX_CENTIDEG / 2;
+    let mut tilt_angle = MAX_CENTIDEG / 2;
+    pan.write(pulse_for(pan_angle));
+    tilt.write(pulse_for(tilt_angle));
+
+    loop {
+        let cmd = COMMAND_CHANNEL.receive().await;
+        let (axis, target) = match cmd {
+            ServoCommand::Absolute { axis, centideg } => (axis, centideg),
+            ServoCommand::Relative { axis, delta } => {
+                let current = match axis {
+                    Axis::Pan => pan_angle,
+                    Axis::Tilt => tilt_angle,
+                };
+                let next = (current as i32 + delta as i32).clamp(0, MAX_CENTIDEG as i32);
+                (axis, next as u16)
+            }
+            ServoCommand::Home { axis } => (axis, MAX_CENTIDEG / 2),
+        };
+
+        match axis {
+            Axis::Pan => {
+                pan_angle = target;
+                pan.write(pulse_for(target));
+            }
+            Axis::Tilt => {
+                tilt_angle = target;
+                tilt.write(pulse_for(target));
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Desktop TCP throughput peer for the Pico W benchmark mode.
+//!
+//! The device runs a TX phase then an RX phase on a single connection, so the
+//! peer is **duplex**: one thread drains everything the device sends (measuring
+//! the device's TX phase) while another blasts zeros (feeding the device's RX
+//! phase). One pair of threads per connection lets a single listener serve
+//! repeated benchmark runs.
+//!
+//! Usage: `perf-server [bind-addr]` (default `0.0.0.0:1234`).
+
+use std::env;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Instant;
+
+const BUF_LEN: usize = 4096;
+
+fn main() -> std::io::Result<()> {
+    let addr = env::args().nth(1).unwrap_or_else(|| "0.0.0.0:1234".to_string());
+
+    let listener = TcpListener::bind(&addr)?;
+    println!("perf-server listening on {addr} (duplex)");
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                thread::spawn(move || {
+                    if let Err(e) = handle(stream) {
+                        eprintln!("connection error: {e}");
+                    }
+                });
+            }
+            Err(e) => eprintln!("accept error: {e}"),
+        }
+    }
+
+    Ok(())
+}
+
+fn handle(stream: TcpStream) -> std::io::Result<()> {
+    let peer = stream.peer_addr()?;
+    println!("connection from {peer}");
+
+    let mut reader = stream.try_clone()?;
+    let mut writer = stream;
+
+    // Drain everything the device sends (its TX phase).
+    let rx = thread::spawn(move || {
+        let mut buf = [0u8; BUF_LEN];
+        let mut total: u64 = 0;
+        let start = Instant::now();
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => total += n as u64,
+                Err(e) => {
+                    eprintln!("read ended: {e}");
+                    break;
+                }
+            }
+        }
+        (total, start.elapsed())
+    });
+
+    // Blast zeros for the device to read (its RX phase).
+    let buf = [0u8; BUF_LEN];
+    let mut tx_total: u64 = 0;
+    loop {
+        match writer.write(&buf) {
+            Ok(0) => break,
+            Ok(n) => tx_total += n as u64,
+            Err(_) => break, // peer closed once the bench finished
+        }
+    }
+
+    let (rx_total, rx_elapsed) = rx.join().unwrap_or((0, Instant::now().elapsed()));
+    println!(
+        "{peer}: received {} bytes in {:.3} s = {:.2} Mbps, sent {} bytes",
+        rx_total,
+        rx_elapsed.as_secs_f64(),
+        mbps(rx_total, rx_elapsed.as_secs_f64()),
+        tx_total,
+    );
+    Ok(())
+}
+
+fn mbps(bytes: u64, secs: f64) -> f64 {
+    if secs > 0.0 {
+        (bytes as f64 * 8.0) / (secs * 1_000_000.0)
+    } else {
+        0.0
+    }
+}